@@ -0,0 +1,35 @@
+// Copyright (c) 2021 Michael J. Simms. All rights reserved.
+
+pub struct CadenceAnalyzer {
+    pub max_cadence: f64,
+    pub readings: Vec<f64>,
+    pub timestamps_ms: Vec<u64>,
+}
+
+impl CadenceAnalyzer {
+    pub fn new() -> CadenceAnalyzer {
+        CadenceAnalyzer {
+            max_cadence: 0.0,
+            readings: Vec::new(),
+            timestamps_ms: Vec::new(),
+        }
+    }
+
+    /// Appends a single cadence reading (in RPM) taken at `time_ms`.
+    pub fn append_sensor_value(&mut self, time_ms: u64, value: f64) {
+        if value > self.max_cadence {
+            self.max_cadence = value;
+        }
+
+        self.readings.push(value);
+        self.timestamps_ms.push(time_ms);
+    }
+
+    pub fn compute_average(&self) -> f64 {
+        if self.readings.is_empty() {
+            return 0.0;
+        }
+
+        self.readings.iter().sum::<f64>() / self.readings.len() as f64
+    }
+}
@@ -0,0 +1,68 @@
+// Copyright (c) 2021 Michael J. Simms. All rights reserved.
+
+// MET thresholds used to bucket each sample into an intensity band.
+const MET_INACTIVE_MAX: f64 = 1.5;
+const MET_LOW_MAX: f64 = 3.0;
+const MET_MODERATE_MAX: f64 = 6.0;
+
+/// Estimates a MET value from running speed, e.g. MET ~= speed_m_s * 3.5.
+pub fn met_from_speed(speed_m_s: f64) -> f64 {
+    speed_m_s * 3.5
+}
+
+/// Estimates a MET value from power output and body mass, e.g. MET ~= (watts / mass_kg) / 3.5.
+pub fn met_from_power(watts: f64, mass_kg: f64) -> f64 {
+    if mass_kg <= 0.0 {
+        return 0.0;
+    }
+
+    (watts / mass_kg) / 3.5
+}
+
+pub struct CalorieAnalyzer {
+    mass_kg: f64,
+    last_time_ms: Option<u64>,
+    pub total_calories: f64,
+    pub inactive_minutes: f64,
+    pub low_intensity_minutes: f64,
+    pub moderate_intensity_minutes: f64,
+    pub vigorous_intensity_minutes: f64,
+}
+
+impl CalorieAnalyzer {
+    pub fn new(mass_kg: f64) -> CalorieAnalyzer {
+        CalorieAnalyzer {
+            mass_kg,
+            last_time_ms: None,
+            total_calories: 0.0,
+            inactive_minutes: 0.0,
+            low_intensity_minutes: 0.0,
+            moderate_intensity_minutes: 0.0,
+            vigorous_intensity_minutes: 0.0,
+        }
+    }
+
+    /// Accumulates calories burned and intensity minutes for the time elapsed since the
+    /// previous sample, given a MET value for the current sample.
+    pub fn append_sample(&mut self, time_ms: u64, met: f64) {
+        if let Some(last_time_ms) = self.last_time_ms {
+            let minutes = (time_ms - last_time_ms) as f64 / 60000.0;
+
+            if self.mass_kg > 0.0 {
+                self.total_calories += met * 3.5 * self.mass_kg / 200.0 * minutes;
+            }
+
+            if met < MET_INACTIVE_MAX {
+                self.inactive_minutes += minutes;
+            } else if met < MET_LOW_MAX {
+                self.low_intensity_minutes += minutes;
+            } else if met < MET_MODERATE_MAX {
+                self.moderate_intensity_minutes += minutes;
+            } else {
+                self.vigorous_intensity_minutes += minutes;
+            }
+        }
+
+        self.last_time_ms = Some(time_ms);
+    }
+}
@@ -0,0 +1,68 @@
+// Copyright (c) 2021 Michael J. Simms. All rights reserved.
+
+pub struct HeartRateAnalyzer {
+    pub max_heart_rate: f64,
+    pub readings: Vec<f64>,
+    pub timestamps_ms: Vec<u64>,
+}
+
+impl HeartRateAnalyzer {
+    pub fn new() -> HeartRateAnalyzer {
+        HeartRateAnalyzer {
+            max_heart_rate: 0.0,
+            readings: Vec::new(),
+            timestamps_ms: Vec::new(),
+        }
+    }
+
+    /// Appends a single heart rate reading (in bpm) taken at `time_ms`.
+    pub fn append_sensor_value(&mut self, time_ms: u64, value: f64) {
+        if value > self.max_heart_rate {
+            self.max_heart_rate = value;
+        }
+
+        self.readings.push(value);
+        self.timestamps_ms.push(time_ms);
+    }
+
+    pub fn compute_average(&self) -> f64 {
+        if self.readings.is_empty() {
+            return 0.0;
+        }
+
+        self.readings.iter().sum::<f64>() / self.readings.len() as f64
+    }
+
+    /// Buckets the time spent at each reading into one of five heart rate zones, expressed as a
+    /// percentage of `max_hr`: Zone 1 <60%, Zone 2 60-70%, Zone 3 70-80%, Zone 4 80-90%, Zone 5
+    /// >90%. Each reading is weighted by the number of seconds since the previous reading, so the
+    /// result is real seconds spent in each zone rather than a simple sample count.
+    pub fn compute_zones(&self, max_hr: f64) -> [f64; 5] {
+        let mut zone_seconds = [0.0; 5];
+
+        if max_hr <= 0.0 {
+            return zone_seconds;
+        }
+
+        for i in 1..self.readings.len() {
+            let delta_secs = (self.timestamps_ms[i] - self.timestamps_ms[i - 1]) as f64 / 1000.0;
+            let pct_of_max = self.readings[i] / max_hr;
+
+            let zone = if pct_of_max < 0.6 {
+                0
+            } else if pct_of_max < 0.7 {
+                1
+            } else if pct_of_max < 0.8 {
+                2
+            } else if pct_of_max < 0.9 {
+                3
+            } else {
+                4
+            };
+
+            zone_seconds[zone] += delta_secs;
+        }
+
+        zone_seconds
+    }
+}
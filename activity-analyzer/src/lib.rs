@@ -1,5 +1,6 @@
 // Copyright (c) 2021 Michael J. Simms. All rights reserved.
 
+extern crate fitparser;
 extern crate gpx;
 extern crate serde;
 extern crate serde_json;
@@ -10,6 +11,7 @@ mod cadence_analyzer;
 mod location_analyzer;
 mod power_analyzer;
 mod heart_rate_analyzer;
+mod calorie_analyzer;
 
 use wasm_bindgen::prelude::*;
 use std::io::BufReader;
@@ -29,13 +31,74 @@ pub fn greet() {
     alert("Copyright (c) 2021 Michael J. Simms. All rights reserved.");
 }
 
-fn make_final_report(analyzer: &location_analyzer::LocationAnalyzer, power_analyzer: Option<&power_analyzer::PowerAnalyzer>, cadence_analyzer: Option<&cadence_analyzer::CadenceAnalyzer>) -> String {
+// FIT stores timestamps as seconds since its own epoch, 1989-12-31T00:00:00Z.
+const FIT_EPOCH_OFFSET_SECS: i64 = 631065600;
+
+fn fit_semicircles_to_degrees(semicircles: f64) -> f64 {
+    semicircles * (180.0 / 2_147_483_648.0)
+}
+
+fn fit_altitude_to_meters(raw: f64) -> f64 {
+    (raw / 5.0) - 500.0
+}
+
+fn fit_field_as_f64(field: &fitparser::FitDataField) -> Option<f64> {
+    match field.value() {
+        fitparser::Value::SInt8(v) => Some(*v as f64),
+        fitparser::Value::UInt8(v) => Some(*v as f64),
+        fitparser::Value::SInt16(v) => Some(*v as f64),
+        fitparser::Value::UInt16(v) => Some(*v as f64),
+        fitparser::Value::SInt32(v) => Some(*v as f64),
+        fitparser::Value::UInt32(v) => Some(*v as f64),
+        fitparser::Value::Float32(v) => Some(*v as f64),
+        fitparser::Value::Float64(v) => Some(*v),
+        _ => None,
+    }
+}
+
+fn fit_field_as_string(field: &fitparser::FitDataField) -> Option<String> {
+    match field.value() {
+        fitparser::Value::String(v) => Some(v.clone()),
+        _ => None,
+    }
+}
+
+// Standard durations, in seconds, used when searching for a cyclist's best average power.
+const BEST_POWER_5_SEC: usize = 5;
+const BEST_POWER_1_MIN: usize = 60;
+const BEST_POWER_5_MIN: usize = 5 * 60;
+const BEST_POWER_20_MIN: usize = 20 * 60;
+
+fn is_cycling_activity(activity_type: &str) -> bool {
+    let activity_type = activity_type.to_lowercase();
+
+    activity_type.contains("bik") || activity_type.contains("cycl")
+}
+
+fn make_final_report(analyzer: &location_analyzer::LocationAnalyzer, power_analyzer: Option<&power_analyzer::PowerAnalyzer>, cadence_analyzer: Option<&cadence_analyzer::CadenceAnalyzer>, heart_rate_analyzer: Option<&heart_rate_analyzer::HeartRateAnalyzer>, calorie_analyzer: Option<&calorie_analyzer::CalorieAnalyzer>, max_hr: f64, ftp: f64) -> String {
+    let is_cycling = is_cycling_activity(&analyzer.activity_type);
+    // Running cadence is typically recorded per foot strike; double it to get steps per minute.
+    let cadence_multiplier = if is_cycling { 1.0 } else { 2.0 };
+
     let mut max_power = 0.0;
     let mut avg_power = 0.0;
     let mut power_readings = Vec::<f64>::new();
+    let mut normalized_power = 0.0;
+    let mut intensity_factor = 0.0;
+    let mut training_stress_score = 0.0;
+    let mut best_power_5s = 0.0;
+    let mut best_power_1min = 0.0;
+    let mut best_power_5min = 0.0;
+    let mut best_power_20min = 0.0;
     let mut max_cadence = 0.0;
     let mut avg_cadence = 0.0;
     let mut cadence_readings = Vec::<f64>::new();
+    let mut max_heart_rate = 0.0;
+    let mut avg_heart_rate = 0.0;
+    let mut heart_rate_zones = [0.0; 5];
+    let mut total_calories = 0.0;
+    let mut moderate_intensity_minutes = 0.0;
+    let mut vigorous_intensity_minutes = 0.0;
 
     match power_analyzer {
         None => {
@@ -44,6 +107,15 @@ fn make_final_report(analyzer: &location_analyzer::LocationAnalyzer, power_analy
             max_power = power_analyzer.max_power;
             avg_power = power_analyzer.compute_average();
             //power_readings = power_analyzer.readings;
+
+            normalized_power = power_analyzer.compute_normalized_power();
+            intensity_factor = power_analyzer.compute_intensity_factor(ftp);
+            training_stress_score = power_analyzer.compute_training_stress_score(ftp);
+
+            best_power_5s = power_analyzer.compute_best_average_power(BEST_POWER_5_SEC);
+            best_power_1min = power_analyzer.compute_best_average_power(BEST_POWER_1_MIN);
+            best_power_5min = power_analyzer.compute_best_average_power(BEST_POWER_5_MIN);
+            best_power_20min = power_analyzer.compute_best_average_power(BEST_POWER_20_MIN);
         }
     }
 
@@ -51,43 +123,81 @@ fn make_final_report(analyzer: &location_analyzer::LocationAnalyzer, power_analy
         None => {
         }
         Some(cadence_analyzer) => {
-            max_cadence = cadence_analyzer.max_cadence;
-            avg_cadence = cadence_analyzer.compute_average();
+            max_cadence = cadence_analyzer.max_cadence * cadence_multiplier;
+            avg_cadence = cadence_analyzer.compute_average() * cadence_multiplier;
             //cadence_readings = cadence_analyzer.readings;
         }
     }
 
-    let analysis_report_str = serde_json::json!({
+    match heart_rate_analyzer {
+        None => {
+        }
+        Some(heart_rate_analyzer) => {
+            max_heart_rate = heart_rate_analyzer.max_heart_rate;
+            avg_heart_rate = heart_rate_analyzer.compute_average();
+            heart_rate_zones = heart_rate_analyzer.compute_zones(max_hr);
+        }
+    }
+
+    match calorie_analyzer {
+        None => {
+        }
+        Some(calorie_analyzer) => {
+            total_calories = calorie_analyzer.total_calories;
+            moderate_intensity_minutes = calorie_analyzer.moderate_intensity_minutes;
+            vigorous_intensity_minutes = calorie_analyzer.vigorous_intensity_minutes;
+        }
+    }
+
+    let mut report = serde_json::json!({
+        "Activity Type": analyzer.activity_type,
         "Start Time (ms)": analyzer.start_time_ms,
         "End Time (ms)": analyzer.last_time_ms,
         "Elapsed Time": (analyzer.last_time_ms - analyzer.start_time_ms) / 1000,
         "Total Distance": analyzer.total_distance,
         "Total Vertical Distance": analyzer.total_vertical,
         "Average Speed": analyzer.avg_speed,
-        "Best 1K": analyzer.get_best_time(location_analyzer::BEST_1K),
-        "Best Mile": analyzer.get_best_time(location_analyzer::BEST_MILE),
-        "Best 5K": analyzer.get_best_time(location_analyzer::BEST_5K),
-        "Best 10K": analyzer.get_best_time(location_analyzer::BEST_10K),
-        "Best 15K": analyzer.get_best_time(location_analyzer::BEST_15K),
-        "Best Half Marathon": analyzer.get_best_time(location_analyzer::BEST_HALF_MARATHON),
-        "Best Marathon": analyzer.get_best_time(location_analyzer::BEST_MARATHON),
-        "Mile Splits": analyzer.mile_splits,
-        "KM Splits": analyzer.km_splits,
         "Times": analyzer.speed_times,
         "Speeds": analyzer.speed_graph,
         "Maximum Power": max_power,
         "Average Power": avg_power,
         "Power Readings": power_readings,
+        "Normalized Power": normalized_power,
+        "Intensity Factor": intensity_factor,
+        "Training Stress Score": training_stress_score,
         "Maximum Cadence": max_cadence,
         "Average Cadence": avg_cadence,
-        "Cadence Readings": cadence_readings
-    }).to_string();
+        "Cadence Readings": cadence_readings,
+        "Maximum Heart Rate": max_heart_rate,
+        "Average Heart Rate": avg_heart_rate,
+        "Heart Rate Zones": heart_rate_zones,
+        "Total Calories": total_calories,
+        "Moderate Intensity Minutes": moderate_intensity_minutes,
+        "Vigorous Intensity Minutes": vigorous_intensity_minutes
+    });
 
-    analysis_report_str
+    if is_cycling {
+        report["Best Average Power (5s)"] = serde_json::json!(best_power_5s);
+        report["Best Average Power (1min)"] = serde_json::json!(best_power_1min);
+        report["Best Average Power (5min)"] = serde_json::json!(best_power_5min);
+        report["Best Average Power (20min)"] = serde_json::json!(best_power_20min);
+    } else {
+        report["Best 1K"] = serde_json::json!(analyzer.get_best_time(location_analyzer::BEST_1K));
+        report["Best Mile"] = serde_json::json!(analyzer.get_best_time(location_analyzer::BEST_MILE));
+        report["Best 5K"] = serde_json::json!(analyzer.get_best_time(location_analyzer::BEST_5K));
+        report["Best 10K"] = serde_json::json!(analyzer.get_best_time(location_analyzer::BEST_10K));
+        report["Best 15K"] = serde_json::json!(analyzer.get_best_time(location_analyzer::BEST_15K));
+        report["Best Half Marathon"] = serde_json::json!(analyzer.get_best_time(location_analyzer::BEST_HALF_MARATHON));
+        report["Best Marathon"] = serde_json::json!(analyzer.get_best_time(location_analyzer::BEST_MARATHON));
+        report["Mile Splits"] = serde_json::json!(analyzer.mile_splits);
+        report["KM Splits"] = serde_json::json!(analyzer.km_splits);
+    }
+
+    report.to_string()
 }
 
 #[wasm_bindgen]
-pub fn analyze_gpx(s: &str) -> String {
+pub fn analyze_gpx(s: &str, mass_kg: f64) -> String {
     let mut analysis_report_str = String::new();
 
     let data = BufReader::new(s.as_bytes());
@@ -96,6 +206,7 @@ pub fn analyze_gpx(s: &str) -> String {
     match res {
         Ok(gpx) => {
             let mut analyzer = location_analyzer::LocationAnalyzer::new();
+            let mut calorie_analyzer = calorie_analyzer::CalorieAnalyzer::new(mass_kg);
 
             // Iterate through the tracks.
             for track in gpx.tracks {
@@ -115,9 +226,14 @@ pub fn analyze_gpx(s: &str) -> String {
                         let lat = point.point().y();
                         let lon = point.point().x();
                         let alt = point.elevation.unwrap();
+                        let time_ms = (time * 1000) as u64;
 
-                        analyzer.append_location((time * 1000) as u64, lat, lon, alt);
+                        analyzer.append_location(time_ms, lat, lon, alt);
                         analyzer.update_speeds();
+
+                        let speed = analyzer.speed_graph.last().copied().unwrap_or(0.0);
+                        let met = calorie_analyzer::met_from_speed(speed);
+                        calorie_analyzer.append_sample(time_ms, met);
                     }
                 }
             }
@@ -126,7 +242,7 @@ pub fn analyze_gpx(s: &str) -> String {
             analyzer.analyze();
 
             // Copy items to the final report.
-            analysis_report_str = make_final_report(&analyzer, None, None);
+            analysis_report_str = make_final_report(&analyzer, None, None, None, Some(&calorie_analyzer), 0.0, 0.0);
         }
         Err(_e) => {
             alert("Error parsing GPX file.");
@@ -137,24 +253,32 @@ pub fn analyze_gpx(s: &str) -> String {
 }
 
 #[wasm_bindgen]
-pub fn analyze_tcx(s: &str) -> String {
+pub fn analyze_tcx(s: &str, max_hr: f64, ftp: f64, mass_kg: f64) -> String {
     let mut data = BufReader::new(s.as_bytes());
     let res = tcx::read(&mut data);
     let mut analyzer = location_analyzer::LocationAnalyzer::new();
     let mut cadence_analyzer = cadence_analyzer::CadenceAnalyzer::new();
     let mut power_analyzer = power_analyzer::PowerAnalyzer::new();
+    let mut heart_rate_analyzer = heart_rate_analyzer::HeartRateAnalyzer::new();
+    let mut calorie_analyzer = calorie_analyzer::CalorieAnalyzer::new(mass_kg);
     let activities = res.activities.unwrap();
 
     for activity in activities.activities {
+        analyzer.set_activity_type(activity.sport.to_string());
+
         for lap in activity.laps {
             for track in lap.tracks {
                 for trackpoint in track.trackpoints {
                     let time = trackpoint.time.timestamp() * 1000 + trackpoint.time.timestamp_subsec_millis() as i64;
-                    let position = trackpoint.position.unwrap();
-                    let altitude = trackpoint.altitude_meters.unwrap();
 
-                    analyzer.append_location(time as u64, position.latitude, position.longitude, altitude);
-                    analyzer.update_speeds();
+                    // A trackpoint may have no position/altitude at all (a brief fix-loss
+                    // mid-ride, or an indoor-trainer export with no GPS), so only touch the
+                    // location analyzer when both are actually present; cadence/power/heart
+                    // rate/MET are still recorded for the sample either way.
+                    if let (Some(position), Some(altitude)) = (trackpoint.position, trackpoint.altitude_meters) {
+                        analyzer.append_location(time as u64, position.latitude, position.longitude, altitude);
+                        analyzer.update_speeds();
+                    }
 
                     // Get the cadence reading.
                     let cadence = trackpoint.cadence;
@@ -166,7 +290,18 @@ pub fn analyze_tcx(s: &str) -> String {
                         }
                     }
 
+                    // Get the heart rate reading.
+                    let heart_rate = trackpoint.heart_rate_bpm;
+                    match heart_rate {
+                        None => {
+                        }
+                        Some(heart_rate) => {
+                            heart_rate_analyzer.append_sensor_value(time as u64, heart_rate as f64);
+                        }
+                    }
+
                     // Get the extensions.
+                    let mut watts: Option<f64> = None;
                     let extensions = trackpoint.extensions.as_ref();
                     match extensions {
                         None => {
@@ -178,18 +313,27 @@ pub fn analyze_tcx(s: &str) -> String {
                                 None => {
                                 }
                                 Some(tpx) => {
-                                    let watts = tpx.watts;
-                                    match watts {
+                                    match tpx.watts {
                                         None => {
                                         }
-                                        Some(watts) => {
-                                            power_analyzer.append_sensor_value(time as u64, watts as f64);
+                                        Some(tpx_watts) => {
+                                            watts = Some(tpx_watts as f64);
+                                            power_analyzer.append_sensor_value(time as u64, tpx_watts as f64);
                                         }
                                     }
                                 }
                             }
                         }
                     }
+
+                    // Estimate the MET value for this sample, preferring power over speed when a
+                    // power meter is present. met_from_power needs a body mass to work with, so
+                    // fall back to the speed-based estimate when mass_kg wasn't supplied.
+                    let met = match watts {
+                        Some(watts) if mass_kg > 0.0 => calorie_analyzer::met_from_power(watts, mass_kg),
+                        _ => calorie_analyzer::met_from_speed(analyzer.speed_graph.last().copied().unwrap_or(0.0)),
+                    };
+                    calorie_analyzer.append_sample(time as u64, met);
                 }
             }
         }
@@ -199,7 +343,173 @@ pub fn analyze_tcx(s: &str) -> String {
     analyzer.analyze();
 
     // Copy items to the final report.
-    let analysis_report_str = make_final_report(&analyzer, Some(&power_analyzer), Some(&cadence_analyzer));
+    let analysis_report_str = make_final_report(&analyzer, Some(&power_analyzer), Some(&cadence_analyzer), Some(&heart_rate_analyzer), Some(&calorie_analyzer), max_hr, ftp);
 
     analysis_report_str
 }
+
+#[wasm_bindgen]
+pub fn analyze_fit(data: &[u8], max_hr: f64, ftp: f64) -> String {
+    let mut analysis_report_str = String::new();
+
+    let res = fitparser::from_bytes(data);
+
+    match res {
+        Ok(records) => {
+            let mut analyzer = location_analyzer::LocationAnalyzer::new();
+            let mut cadence_analyzer = cadence_analyzer::CadenceAnalyzer::new();
+            let mut power_analyzer = power_analyzer::PowerAnalyzer::new();
+            let mut heart_rate_analyzer = heart_rate_analyzer::HeartRateAnalyzer::new();
+
+            for record in records {
+                // The Session/Sport messages carry the activity type; every other message we
+                // care about is a Record (one sample).
+                match record.kind() {
+                    fitparser::profile::MesgNum::Session | fitparser::profile::MesgNum::Sport => {
+                        for field in record.fields() {
+                            if field.name() == "sport" {
+                                if let Some(sport) = fit_field_as_string(field) {
+                                    analyzer.set_activity_type(sport);
+                                }
+                            }
+                        }
+                        continue;
+                    }
+                    fitparser::profile::MesgNum::Record => {}
+                    _ => continue,
+                }
+
+                let mut raw_lat: Option<f64> = None;
+                let mut raw_lon: Option<f64> = None;
+                let mut raw_altitude: Option<f64> = None;
+                let mut raw_timestamp: Option<f64> = None;
+                let mut power: Option<f64> = None;
+                let mut cadence: Option<f64> = None;
+                let mut heart_rate: Option<f64> = None;
+
+                // Pull out just the fields this analyzer cares about.
+                for field in record.fields() {
+                    match field.name() {
+                        "position_lat" => raw_lat = fit_field_as_f64(field),
+                        "position_long" => raw_lon = fit_field_as_f64(field),
+                        "altitude" => raw_altitude = fit_field_as_f64(field),
+                        "timestamp" => raw_timestamp = fit_field_as_f64(field),
+                        "power" => power = fit_field_as_f64(field),
+                        "cadence" => cadence = fit_field_as_f64(field),
+                        "heart_rate" => heart_rate = fit_field_as_f64(field),
+                        _ => {}
+                    }
+                }
+
+                // A record message without a timestamp can't be attributed to any point in time,
+                // so skip it entirely. Position/altitude are independently optional from here on
+                // (e.g. an indoor trainer ride has power/cadence/heart rate but no GPS fix), so
+                // location and sensor readings are each recorded whenever their own field is
+                // present.
+                if let Some(timestamp) = raw_timestamp {
+                    let time_ms = ((timestamp as i64 + FIT_EPOCH_OFFSET_SECS) * 1000) as u64;
+
+                    if let (Some(lat), Some(lon), Some(alt)) = (raw_lat, raw_lon, raw_altitude) {
+                        let lat_deg = fit_semicircles_to_degrees(lat);
+                        let lon_deg = fit_semicircles_to_degrees(lon);
+                        let alt_m = fit_altitude_to_meters(alt);
+
+                        analyzer.append_location(time_ms, lat_deg, lon_deg, alt_m);
+                        analyzer.update_speeds();
+                    }
+
+                    if let Some(cadence) = cadence {
+                        cadence_analyzer.append_sensor_value(time_ms, cadence);
+                    }
+                    if let Some(power) = power {
+                        power_analyzer.append_sensor_value(time_ms, power);
+                    }
+                    if let Some(heart_rate) = heart_rate {
+                        heart_rate_analyzer.append_sensor_value(time_ms, heart_rate);
+                    }
+                }
+            }
+
+            // For calculations that only make sense once all the points have been added.
+            analyzer.analyze();
+
+            // Copy items to the final report.
+            analysis_report_str = make_final_report(&analyzer, Some(&power_analyzer), Some(&cadence_analyzer), Some(&heart_rate_analyzer), None, max_hr, ftp);
+        }
+        Err(_e) => {
+            alert("Error parsing FIT file.");
+        }
+    }
+
+    analysis_report_str
+}
+
+#[wasm_bindgen]
+pub fn export_line_protocol(s: &str) -> String {
+    let mut data = BufReader::new(s.as_bytes());
+    let res = tcx::read(&mut data);
+    let mut analyzer = location_analyzer::LocationAnalyzer::new();
+    let activities = res.activities.unwrap();
+    let mut lines = Vec::<String>::new();
+
+    for activity in activities.activities {
+        analyzer.set_activity_type(activity.sport.to_string());
+
+        for lap in activity.laps {
+            for track in lap.tracks {
+                for trackpoint in track.trackpoints {
+                    let time = trackpoint.time.timestamp() * 1000 + trackpoint.time.timestamp_subsec_millis() as i64;
+                    let mut fields = Vec::<String>::new();
+
+                    // A trackpoint from an indoor trainer (or any watch without GPS) may have
+                    // no position/altitude at all, so only touch the location analyzer and emit
+                    // those fields when both are actually present.
+                    if let (Some(position), Some(altitude)) = (trackpoint.position, trackpoint.altitude_meters) {
+                        analyzer.append_location(time as u64, position.latitude, position.longitude, altitude);
+                        analyzer.update_speeds();
+
+                        let speed = analyzer.speed_graph.last().copied().unwrap_or(0.0);
+                        let distance = analyzer.total_distance;
+
+                        fields.push(format!("speed={}", speed));
+                        fields.push(format!("altitude={}", altitude));
+                        fields.push(format!("distance={}", distance));
+                    }
+
+                    if let Some(cadence) = trackpoint.cadence {
+                        fields.push(format!("cadence={}", cadence));
+                    }
+
+                    if let Some(heart_rate) = trackpoint.heart_rate_bpm {
+                        fields.push(format!("heart_rate={}", heart_rate));
+                    }
+
+                    if let Some(extensions) = trackpoint.extensions.as_ref() {
+                        if let Some(tpx) = extensions.tpx.as_ref() {
+                            if let Some(watts) = tpx.watts {
+                                fields.push(format!("power={}", watts));
+                            }
+                        }
+                    }
+
+                    // Nothing to report for this trackpoint at all, so there's no line to emit.
+                    if fields.is_empty() {
+                        continue;
+                    }
+
+                    // InfluxDB line protocol timestamps are nanoseconds since the Unix epoch.
+                    let timestamp_ns = time * 1_000_000;
+
+                    lines.push(format!(
+                        "activity,type={} {} {}",
+                        analyzer.activity_type.replace(' ', "_"),
+                        fields.join(","),
+                        timestamp_ns
+                    ));
+                }
+            }
+        }
+    }
+
+    lines.join("\n")
+}
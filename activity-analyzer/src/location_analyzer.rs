@@ -0,0 +1,177 @@
+// Copyright (c) 2021 Michael J. Simms. All rights reserved.
+
+use crate::utils;
+
+// Target distances, in meters, used when searching for "best effort" times.
+pub const BEST_1K: f64 = 1000.0;
+pub const BEST_MILE: f64 = 1609.34;
+pub const BEST_5K: f64 = 5000.0;
+pub const BEST_10K: f64 = 10000.0;
+pub const BEST_15K: f64 = 15000.0;
+pub const BEST_HALF_MARATHON: f64 = 21097.5;
+pub const BEST_MARATHON: f64 = 42195.0;
+
+const METERS_PER_MILE: f64 = 1609.34;
+const METERS_PER_KM: f64 = 1000.0;
+
+pub struct LocationAnalyzer {
+    pub activity_type: String,
+    pub start_time_ms: u64,
+    pub last_time_ms: u64,
+    pub total_distance: f64,   // meters
+    pub total_vertical: f64,   // meters of ascent
+    pub avg_speed: f64,        // meters/second
+    pub speed_graph: Vec<f64>, // meters/second, one entry per appended point
+    pub speed_times: Vec<u64>, // ms, parallel to speed_graph
+    pub mile_splits: Vec<f64>, // seconds per completed mile
+    pub km_splits: Vec<f64>,   // seconds per completed km
+
+    have_last_point: bool,
+    last_lat: f64,
+    last_lon: f64,
+    last_alt: f64,
+
+    // Cumulative distance/time, sampled once per appended point, used to compute best efforts.
+    cumulative_distances: Vec<f64>,
+    cumulative_times_ms: Vec<u64>,
+
+    mile_mark_distance: f64,
+    mile_mark_time_ms: u64,
+    km_mark_distance: f64,
+    km_mark_time_ms: u64,
+}
+
+impl LocationAnalyzer {
+    pub fn new() -> LocationAnalyzer {
+        LocationAnalyzer {
+            activity_type: String::new(),
+            start_time_ms: 0,
+            last_time_ms: 0,
+            total_distance: 0.0,
+            total_vertical: 0.0,
+            avg_speed: 0.0,
+            speed_graph: Vec::new(),
+            speed_times: Vec::new(),
+            mile_splits: Vec::new(),
+            km_splits: Vec::new(),
+            have_last_point: false,
+            last_lat: 0.0,
+            last_lon: 0.0,
+            last_alt: 0.0,
+            cumulative_distances: Vec::new(),
+            cumulative_times_ms: Vec::new(),
+            mile_mark_distance: 0.0,
+            mile_mark_time_ms: 0,
+            km_mark_distance: 0.0,
+            km_mark_time_ms: 0,
+        }
+    }
+
+    /// Records the sport/activity type (e.g. "running", "biking") as reported by the file format.
+    pub fn set_activity_type(&mut self, activity_type: String) {
+        self.activity_type = activity_type;
+    }
+
+    /// Appends a single location reading to the analyzer, updating distance and split tracking.
+    pub fn append_location(&mut self, time_ms: u64, lat: f64, lon: f64, alt: f64) {
+        if !self.have_last_point {
+            self.start_time_ms = time_ms;
+            self.mile_mark_time_ms = time_ms;
+            self.km_mark_time_ms = time_ms;
+            self.have_last_point = true;
+        } else {
+            let delta = utils::haversine_distance(self.last_lat, self.last_lon, lat, lon);
+            self.total_distance += delta;
+
+            if alt > self.last_alt {
+                self.total_vertical += alt - self.last_alt;
+            }
+
+            self.update_splits(time_ms);
+        }
+
+        self.last_lat = lat;
+        self.last_lon = lon;
+        self.last_alt = alt;
+        self.last_time_ms = time_ms;
+
+        self.cumulative_distances.push(self.total_distance);
+        self.cumulative_times_ms.push(time_ms);
+    }
+
+    /// Checks whether a mile or km boundary was just crossed and, if so, records the split time.
+    fn update_splits(&mut self, time_ms: u64) {
+        while self.total_distance - self.mile_mark_distance >= METERS_PER_MILE {
+            self.mile_splits.push((time_ms - self.mile_mark_time_ms) as f64 / 1000.0);
+            self.mile_mark_distance += METERS_PER_MILE;
+            self.mile_mark_time_ms = time_ms;
+        }
+
+        while self.total_distance - self.km_mark_distance >= METERS_PER_KM {
+            self.km_splits.push((time_ms - self.km_mark_time_ms) as f64 / 1000.0);
+            self.km_mark_distance += METERS_PER_KM;
+            self.km_mark_time_ms = time_ms;
+        }
+    }
+
+    /// Recomputes the instantaneous speed from the two most recent points and appends it to the
+    /// speed graph. Should be called once per appended location.
+    pub fn update_speeds(&mut self) {
+        let num_points = self.cumulative_distances.len();
+
+        if num_points < 2 {
+            self.speed_graph.push(0.0);
+            self.speed_times.push(self.last_time_ms);
+            return;
+        }
+
+        let delta_distance = self.cumulative_distances[num_points - 1] - self.cumulative_distances[num_points - 2];
+        let delta_time_ms = self.cumulative_times_ms[num_points - 1] - self.cumulative_times_ms[num_points - 2];
+
+        let speed = if delta_time_ms > 0 {
+            delta_distance / (delta_time_ms as f64 / 1000.0)
+        } else {
+            0.0
+        };
+
+        self.speed_graph.push(speed);
+        self.speed_times.push(self.last_time_ms);
+    }
+
+    /// Performs the calculations that only make sense once every point has been added.
+    pub fn analyze(&mut self) {
+        let elapsed_seconds = (self.last_time_ms - self.start_time_ms) as f64 / 1000.0;
+
+        self.avg_speed = if elapsed_seconds > 0.0 {
+            self.total_distance / elapsed_seconds
+        } else {
+            0.0
+        };
+    }
+
+    /// Finds the shortest amount of time, in seconds, in which `target_distance` meters were
+    /// covered. Returns 0.0 if the activity never covered that distance.
+    pub fn get_best_time(&self, target_distance: f64) -> f64 {
+        let num_points = self.cumulative_distances.len();
+        let mut best_time_secs = 0.0;
+        let mut window_start = 0;
+
+        for window_end in 0..num_points {
+            while self.cumulative_distances[window_end] - self.cumulative_distances[window_start] >= target_distance {
+                let elapsed_ms = self.cumulative_times_ms[window_end] - self.cumulative_times_ms[window_start];
+                let elapsed_secs = elapsed_ms as f64 / 1000.0;
+
+                if best_time_secs == 0.0 || elapsed_secs < best_time_secs {
+                    best_time_secs = elapsed_secs;
+                }
+
+                if window_start == window_end {
+                    break;
+                }
+                window_start += 1;
+            }
+        }
+
+        best_time_secs
+    }
+}
@@ -0,0 +1,145 @@
+// Copyright (c) 2021 Michael J. Simms. All rights reserved.
+
+use std::collections::VecDeque;
+
+// Width, in seconds, of the rolling average used when computing Normalized Power.
+const NORMALIZED_POWER_WINDOW_SECS: usize = 30;
+
+pub struct PowerAnalyzer {
+    pub max_power: f64,
+    pub readings: Vec<f64>,
+    pub timestamps_ms: Vec<u64>,
+}
+
+impl PowerAnalyzer {
+    pub fn new() -> PowerAnalyzer {
+        PowerAnalyzer {
+            max_power: 0.0,
+            readings: Vec::new(),
+            timestamps_ms: Vec::new(),
+        }
+    }
+
+    /// Appends a single power reading (in watts) taken at `time_ms`.
+    pub fn append_sensor_value(&mut self, time_ms: u64, value: f64) {
+        if value > self.max_power {
+            self.max_power = value;
+        }
+
+        self.readings.push(value);
+        self.timestamps_ms.push(time_ms);
+    }
+
+    pub fn compute_average(&self) -> f64 {
+        if self.readings.is_empty() {
+            return 0.0;
+        }
+
+        self.readings.iter().sum::<f64>() / self.readings.len() as f64
+    }
+
+    /// Resamples the readings to 1-second resolution, holding the last known value between
+    /// samples.
+    fn resample_to_1hz(&self) -> Vec<f64> {
+        if self.readings.is_empty() {
+            return Vec::new();
+        }
+
+        let start_ms = self.timestamps_ms[0];
+        let end_ms = self.timestamps_ms[self.timestamps_ms.len() - 1];
+        let num_seconds = ((end_ms - start_ms) / 1000) as usize + 1;
+
+        let mut resampled = Vec::with_capacity(num_seconds);
+        let mut reading_index = 0;
+
+        for second in 0..num_seconds {
+            let target_ms = start_ms + (second as u64) * 1000;
+
+            while reading_index + 1 < self.readings.len() && self.timestamps_ms[reading_index + 1] <= target_ms {
+                reading_index += 1;
+            }
+
+            resampled.push(self.readings[reading_index]);
+        }
+
+        resampled
+    }
+
+    /// Computes Normalized Power: resample to 1-second resolution, take a rolling 30-second
+    /// average, raise each averaged value to the 4th power, average those, then take the 4th
+    /// root.
+    pub fn compute_normalized_power(&self) -> f64 {
+        let resampled = self.resample_to_1hz();
+
+        if resampled.is_empty() {
+            return 0.0;
+        }
+
+        let num_seconds = resampled.len();
+        let mut window: VecDeque<f64> = VecDeque::with_capacity(NORMALIZED_POWER_WINDOW_SECS);
+        let mut window_sum = 0.0;
+        let mut fourth_power_sum = 0.0;
+
+        for value in resampled {
+            window.push_back(value);
+            window_sum += value;
+
+            if window.len() > NORMALIZED_POWER_WINDOW_SECS {
+                window_sum -= window.pop_front().unwrap();
+            }
+
+            let window_avg = window_sum / window.len() as f64;
+            fourth_power_sum += window_avg.powi(4);
+        }
+
+        (fourth_power_sum / num_seconds as f64).powf(0.25)
+    }
+
+    /// Finds the highest average power sustained for `duration_secs` seconds anywhere in the
+    /// activity (e.g. best 5-second, 1-minute, 5-minute, or 20-minute power).
+    pub fn compute_best_average_power(&self, duration_secs: usize) -> f64 {
+        let resampled = self.resample_to_1hz();
+
+        if duration_secs == 0 || resampled.len() < duration_secs {
+            return 0.0;
+        }
+
+        let mut window_sum: f64 = resampled[0..duration_secs].iter().sum();
+        let mut best_avg = window_sum / duration_secs as f64;
+
+        for i in duration_secs..resampled.len() {
+            window_sum += resampled[i] - resampled[i - duration_secs];
+            let avg = window_sum / duration_secs as f64;
+
+            if avg > best_avg {
+                best_avg = avg;
+            }
+        }
+
+        best_avg
+    }
+
+    /// Intensity Factor: the ratio of Normalized Power to the rider's Functional Threshold Power.
+    pub fn compute_intensity_factor(&self, ftp: f64) -> f64 {
+        if ftp <= 0.0 {
+            return 0.0;
+        }
+
+        self.compute_normalized_power() / ftp
+    }
+
+    /// Training Stress Score, a measure of overall training load for the activity. The duration
+    /// is derived from the power readings' own timestamps rather than the location analyzer's,
+    /// so this still works for a power-only activity with no GPS fix at all.
+    pub fn compute_training_stress_score(&self, ftp: f64) -> f64 {
+        if ftp <= 0.0 || self.timestamps_ms.is_empty() {
+            return 0.0;
+        }
+
+        let duration_secs = (self.timestamps_ms[self.timestamps_ms.len() - 1] - self.timestamps_ms[0]) as f64 / 1000.0;
+        let normalized_power = self.compute_normalized_power();
+        let intensity_factor = normalized_power / ftp;
+
+        (duration_secs * normalized_power * intensity_factor) / (ftp * 3600.0) * 100.0
+    }
+}
@@ -0,0 +1,18 @@
+// Copyright (c) 2021 Michael J. Simms. All rights reserved.
+
+const EARTH_RADIUS_M: f64 = 6371000.0;
+
+/// Computes the great circle distance, in meters, between two lat/lon points (in degrees)
+/// using the haversine formula.
+pub fn haversine_distance(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let lat1_rad = lat1.to_radians();
+    let lat2_rad = lat2.to_radians();
+    let delta_lat = (lat2 - lat1).to_radians();
+    let delta_lon = (lon2 - lon1).to_radians();
+
+    let a = (delta_lat / 2.0).sin().powi(2)
+        + lat1_rad.cos() * lat2_rad.cos() * (delta_lon / 2.0).sin().powi(2);
+    let c = 2.0 * a.sqrt().asin();
+
+    EARTH_RADIUS_M * c
+}